@@ -0,0 +1,113 @@
+//! Minimal ActivityPub / ActivityStreams federation support.
+//!
+//! Publishes newly created `Entry`s to a configurable outbox as `Create`
+//! activities wrapping a `Place` object, and decodes inbound `Create` /
+//! `Update` / `Delete` activities so remote markers can be merged into
+//! the local map.
+
+use crate::{Entry, Msg};
+use futures::Future;
+use seed::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const CONTENT_TYPE: &str = r#"application/ld+json; profile="https://www.w3.org/ns/activitystreams""#;
+pub const CONTENT_TYPE_FALLBACK: &str = "application/activity+json";
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activity {
+    #[serde(rename = "@context")]
+    pub context: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub actor: String,
+    pub to: Vec<String>,
+    pub object: Object,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: Option<String>,
+    pub name: String,
+    pub content: String,
+    pub location: Place,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Place {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Wraps an `Entry` in a `Create` activity addressed to the public collection.
+pub fn entry_to_create_activity(entry: &Entry, actor: &str) -> Activity {
+    Activity {
+        context: "https://www.w3.org/ns/activitystreams".into(),
+        kind: "Create".into(),
+        actor: actor.into(),
+        to: vec![PUBLIC.into()],
+        object: Object {
+            kind: "Place".into(),
+            id: Some(entry.id.clone()),
+            name: entry.title.clone(),
+            content: entry.description.clone(),
+            location: Place {
+                kind: "Place".into(),
+                latitude: entry.lat,
+                longitude: entry.lng,
+            },
+        },
+    }
+}
+
+/// POSTs a `Create` activity to the outbox.
+pub fn publish(activity: &Activity, outbox_url: &str) -> impl Future<Item = Msg, Error = Msg> {
+    log!("publish activity to outbox", activity);
+    seed::fetch::Request::new(outbox_url)
+        .method(seed::fetch::Method::Post)
+        .header("Content-Type", CONTENT_TYPE)
+        .header("Accept", &format!("{}, {}", CONTENT_TYPE, CONTENT_TYPE_FALLBACK))
+        .send_json(activity)
+        .fetch_json_data(|d: seed::fetch::ResponseDataResult<()>| {
+            Msg::ActivityPublished(d.map_err(|e| format!("{:#?}", e)))
+        })
+}
+
+/// What an inbound activity did to `entries`, so the caller can patch just
+/// the affected marker instead of redrawing the whole map.
+pub enum Applied {
+    Upserted(Entry),
+    Removed(String),
+    Ignored,
+}
+
+/// Applies an inbound `Create`/`Update`/`Delete` activity to the given entries.
+pub fn apply_remote_activity(entries: &mut Vec<Entry>, activity: Activity) -> Applied {
+    let id = match activity.object.id.clone() {
+        Some(id) => id,
+        None => return Applied::Ignored,
+    };
+    match activity.kind.as_str() {
+        "Delete" => {
+            entries.retain(|e| e.id != id);
+            Applied::Removed(id)
+        }
+        "Create" | "Update" => {
+            let entry = Entry {
+                id,
+                title: activity.object.name,
+                description: activity.object.content,
+                lat: activity.object.location.latitude,
+                lng: activity.object.location.longitude,
+            };
+            entries.retain(|e| e.id != entry.id);
+            entries.push(entry.clone());
+            Applied::Upserted(entry)
+        }
+        _ => Applied::Ignored,
+    }
+}
@@ -0,0 +1,131 @@
+//! Client-side export of the visible entries to GeoJSON or CSV.
+//!
+//! Builds the file content in memory, wraps it in a `Blob`, and triggers a
+//! synthetic anchor click to download it — no server round-trip involved.
+
+use crate::Entry;
+use js_sys::Array;
+use serde::Serialize;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    GeoJson,
+    Csv,
+}
+
+impl Format {
+    fn mime_type(self) -> &'static str {
+        match self {
+            Format::GeoJson => "application/geo+json",
+            Format::Csv => "text/csv",
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Format::GeoJson => "entries.geojson",
+            Format::Csv => "entries.csv",
+        }
+    }
+
+    fn render(self, entries: &[Entry]) -> String {
+        match self {
+            Format::GeoJson => to_geojson(entries),
+            Format::Csv => to_csv(entries),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FeatureCollection {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<Feature>,
+}
+
+#[derive(Serialize)]
+struct Feature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    geometry: Geometry,
+    properties: Properties,
+}
+
+#[derive(Serialize)]
+struct Geometry {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    coordinates: [f64; 2],
+}
+
+#[derive(Serialize)]
+struct Properties {
+    title: String,
+    description: String,
+}
+
+fn to_geojson(entries: &[Entry]) -> String {
+    let collection = FeatureCollection {
+        kind: "FeatureCollection",
+        features: entries
+            .iter()
+            .map(|e| Feature {
+                kind: "Feature",
+                geometry: Geometry {
+                    kind: "Point",
+                    coordinates: [e.lng, e.lat],
+                },
+                properties: Properties {
+                    title: e.title.clone(),
+                    description: e.description.clone(),
+                },
+            })
+            .collect(),
+    };
+    serde_json::to_string(&collection).unwrap()
+}
+
+fn to_csv(entries: &[Entry]) -> String {
+    let mut csv = String::from("id,title,lat,lng,description\n");
+    for e in entries {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            csv_escape(&e.id),
+            csv_escape(&e.title),
+            e.lat,
+            e.lng,
+            csv_escape(&e.description)
+        ));
+    }
+    csv
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Downloads `entries` in the given `format` via a synthetic anchor click.
+pub fn download(entries: &[Entry], format: Format) {
+    let content = format.render(entries);
+
+    let parts = Array::new();
+    parts.push(&JsValue::from_str(&content));
+    let mut props = BlobPropertyBag::new();
+    props.type_(format.mime_type());
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &props).unwrap();
+    let url = Url::create_object_url_with_blob(&blob).unwrap();
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor: HtmlAnchorElement = document.create_element("a").unwrap().dyn_into().unwrap();
+    anchor.set_href(&url);
+    anchor.set_download(format.file_name());
+    anchor.click();
+
+    Url::revoke_object_url(&url).ok();
+}
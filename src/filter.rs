@@ -0,0 +1,89 @@
+//! Faceted, range-aware filtering of OFDB search results.
+//!
+//! String-valued facets (categories) are turned into query parameters the
+//! API understands; numeric facets (rating, distance) are range bounds —
+//! `>=`/`<=` — rather than exact matches, and distance is applied
+//! client-side since the search endpoint has no notion of "current map
+//! center".
+
+use crate::{ranking, Entry};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Category {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub selected: bool,
+}
+
+pub const DEFAULT_CATEGORIES: [Category; 2] = [
+    Category {
+        id: "2cd00bebec0c48ba9db761da48678134",
+        label: "Commercial",
+        selected: true,
+    },
+    Category {
+        id: "77b3c33a92554bcf8e8c2c86cedd6f6f",
+        label: "Non-commercial",
+        selected: true,
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct FilterState {
+    pub categories: Vec<Category>,
+    pub min_rating: Option<f64>,
+    pub max_distance_km: Option<f64>,
+}
+
+impl Default for FilterState {
+    fn default() -> Self {
+        Self {
+            categories: DEFAULT_CATEGORIES.to_vec(),
+            min_rating: None,
+            max_distance_km: None,
+        }
+    }
+}
+
+impl FilterState {
+    /// Query string fragment for the facets the OFDB search endpoint can
+    /// apply server-side.
+    pub fn query_string(&self, text: &str) -> String {
+        let categories = self
+            .categories
+            .iter()
+            .filter(|c| c.selected)
+            .map(|c| percent_encode(c.id))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut query = format!("text={}&categories={}", percent_encode(text), categories);
+        if let Some(min_rating) = self.min_rating {
+            query.push_str(&format!("&min_rating={}", min_rating));
+        }
+        query
+    }
+
+    /// Client-side predicate for facets the API can't filter, e.g.
+    /// distance from the current map center.
+    pub fn matches(&self, entry: &Entry, center: (f64, f64)) -> bool {
+        match self.max_distance_km {
+            Some(max_distance) => ranking::haversine_km(center.0, center.1, entry.lat, entry.lng) <= max_distance,
+            None => true,
+        }
+    }
+}
+
+/// Percent-encodes a query string component so free-form text (and
+/// defensively, facet ids) can't inject or clobber other parameters.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char);
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -1,10 +1,27 @@
 #[macro_use]
 extern crate seed;
+mod activitystreams;
+mod export;
+mod filter;
+mod ranking;
+mod session;
+mod stream;
+
 use futures::Future;
 use seed::prelude::*;
 use semval::prelude::*;
 use serde::{Deserialize, Serialize};
-use wasm_bindgen::JsValue;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{FormData, HtmlInputElement, Request, RequestInit, RequestMode, Response};
+
+const ACTOR: &str = "https://kvm.example/actor";
+const OUTBOX_URL: &str = "https://kvm.example/outbox";
+const TITLE_MIN: usize = 3;
+const TITLE_MAX: usize = 25;
+const DESCRIPTION_MAX: usize = 1000;
+const MAX_PHOTO_BYTES: u32 = 5_000_000;
+const ALLOWED_PHOTO_MIME_TYPES: [&str; 3] = ["image/jpeg", "image/png", "image/gif"];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MapEntry {
@@ -62,12 +79,28 @@ struct Model {
     pub show_new_entry_form: bool,
     pub new_entry_form: EntryFormModel,
     pub new_entry_form_errors: Vec<EntryFormInvalidity>,
+    pub outbox_url: String,
+    pub session: Option<session::Session>,
+    pub show_login_form: bool,
+    pub login_form: LoginFormModel,
+    pub login_form_error: Option<String>,
+    pub live_mode: bool,
+    pub search_query: String,
+    pub rank_text_weight: f64,
+    pub filter: filter::FilterState,
 }
 
 #[derive(Debug, Default, Clone)]
 struct EntryFormModel {
     title: String,
     description: String,
+    photos: Vec<web_sys::File>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct LoginFormModel {
+    email: String,
+    password: String,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -82,17 +115,36 @@ struct Actual(usize);
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum EntryFormInvalidity {
     TitleLength(Min, Max, Actual),
+    DescriptionLength(Max, Actual),
+    PhotoTooLarge(Max, Actual),
+    PhotoInvalidMimeType(String),
+    Server(String),
 }
 
 impl Validate for EntryFormModel {
     type Invalidity = EntryFormInvalidity;
     fn validate(&self) -> ValidationResult<Self::Invalidity> {
-        ValidationContext::new()
+        let mut context = ValidationContext::new()
             .invalidate_if(
-                self.title.len() < 3,
-                EntryFormInvalidity::TitleLength(Min(3), Max(25), Actual(self.title.len())),
+                self.title.len() < TITLE_MIN,
+                EntryFormInvalidity::TitleLength(Min(TITLE_MIN), Max(TITLE_MAX), Actual(self.title.len())),
             )
-            .into()
+            .invalidate_if(
+                self.description.len() > DESCRIPTION_MAX,
+                EntryFormInvalidity::DescriptionLength(Max(DESCRIPTION_MAX), Actual(self.description.len())),
+            );
+        for photo in &self.photos {
+            context = context
+                .invalidate_if(
+                    photo.size() as u32 > MAX_PHOTO_BYTES,
+                    EntryFormInvalidity::PhotoTooLarge(Max(MAX_PHOTO_BYTES as usize), Actual(photo.size() as usize)),
+                )
+                .invalidate_if(
+                    !ALLOWED_PHOTO_MIME_TYPES.contains(&photo.type_().as_str()),
+                    EntryFormInvalidity::PhotoInvalidMimeType(photo.type_()),
+                );
+        }
+        context.into()
     }
 }
 
@@ -106,6 +158,15 @@ impl Default for Model {
             show_new_entry_form: false,
             new_entry_form: EntryFormModel::default(),
             new_entry_form_errors: vec![],
+            outbox_url: OUTBOX_URL.into(),
+            session: None,
+            show_login_form: false,
+            login_form: LoginFormModel::default(),
+            login_form_error: None,
+            live_mode: true,
+            search_query: String::new(),
+            rank_text_weight: 1.0,
+            filter: filter::FilterState::default(),
         }
     }
 }
@@ -172,7 +233,65 @@ struct Entry {
     pub lng: f64,
 }
 
-fn fetch_entries(bbox: &BBox) -> impl Future<Item = Msg, Error = Msg> {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NewEntryResponse {
+    id: String,
+}
+
+/// Submits the entry as `multipart/form-data` (text fields plus one binary
+/// part per photo) since `seed::fetch` only speaks JSON bodies.
+fn create_entry(
+    form: &EntryFormModel,
+    lat: f64,
+    lng: f64,
+    session: &Option<session::Session>,
+) -> impl Future<Item = Msg, Error = Msg> {
+    log!("create entry (multipart)", form.title);
+    let body = FormData::new().unwrap();
+    body.append_with_str("title", &form.title).unwrap();
+    body.append_with_str("description", &form.description).unwrap();
+    body.append_with_str("lat", &lat.to_string()).unwrap();
+    body.append_with_str("lng", &lng.to_string()).unwrap();
+    for photo in &form.photos {
+        body.append_with_blob("photo", photo).unwrap();
+    }
+
+    let mut init = RequestInit::new();
+    init.method("POST");
+    init.mode(RequestMode::Cors);
+    init.body(Some(&body));
+    let request = Request::new_with_str_and_init("https://api.ofdb.io/v0/entries", &init).unwrap();
+    session::authorize(&request, session);
+
+    JsFuture::from(web_sys::window().unwrap().fetch_with_request(&request))
+        .map_err(|e| format!("{:#?}", e))
+        .and_then(|resp_value| {
+            let resp: Response = resp_value.dyn_into().map_err(|e| format!("{:#?}", e))?;
+            let ok = resp.ok();
+            let status = resp.status();
+            let json = resp.json().map_err(|e| format!("{:#?}", e))?;
+            Ok((ok, status, JsFuture::from(json)))
+        })
+        .and_then(|(ok, status, json)| {
+            json.map_err(|e| format!("{:#?}", e)).and_then(move |json| {
+                if ok {
+                    json.into_serde::<NewEntryResponse>().map_err(|e| format!("{:#?}", e))
+                } else {
+                    let message = json
+                        .into_serde::<serde_json::Value>()
+                        .map(|v| v.to_string())
+                        .unwrap_or_else(|_| format!("request failed with status {}", status));
+                    Err(message)
+                }
+            })
+        })
+        .then(|result: Result<NewEntryResponse, String>| match result {
+            Ok(res) => Ok(Msg::EntryCreated(Ok(res))),
+            Err(e) => Ok(Msg::EntryCreated(Err(e))),
+        })
+}
+
+fn fetch_entries(bbox: &BBox, filter: &filter::FilterState, text: &str) -> impl Future<Item = Msg, Error = Msg> {
     let bbox: String = bbox
         .to_vec()
         .into_iter()
@@ -180,7 +299,11 @@ fn fetch_entries(bbox: &BBox) -> impl Future<Item = Msg, Error = Msg> {
         .collect::<Vec<_>>()
         .join(",");
     log!("fetch entries for {:#?}", bbox);
-    let url = format!("https://api.ofdb.io/v0/search?text=&categories=2cd00bebec0c48ba9db761da48678134,77b3c33a92554bcf8e8c2c86cedd6f6f&bbox={}",bbox);
+    let url = format!(
+        "https://api.ofdb.io/v0/search?{}&bbox={}",
+        filter.query_string(text),
+        bbox
+    );
     seed::fetch::Request::new(url)
         .fetch_json_data(|d| Msg::EntrySearchResult(d.map_err(|e| format!("{:#?}", e))))
 }
@@ -196,12 +319,35 @@ enum Msg {
     ShowNewEntryForm,
     EntryForm(EntryFormMsg),
     CreateNewEntry,
+    ActivityPublished(Result<(), String>),
+    RemoteActivity(Result<activitystreams::Activity, String>),
+    EntryCreated(Result<NewEntryResponse, String>),
+    ShowLoginForm,
+    LoginForm(LoginFormMsg),
+    Login,
+    LoginResult(Result<session::Session, String>),
+    Logout,
+    ToggleLiveMode,
+    StreamEvent(Result<stream::Event, String>),
+    SearchQuery(String),
+    RankTextWeight(f64),
+    Export(export::Format),
+    ToggleFilterCategory(usize),
+    FilterMinRating(Option<f64>),
+    FilterMaxDistanceKm(Option<f64>),
 }
 
 #[derive(Debug, Clone)]
 enum EntryFormMsg {
     Title(String),
     Description(String),
+    Photos(Vec<web_sys::File>),
+}
+
+#[derive(Debug, Clone)]
+enum LoginFormMsg {
+    Email(String),
+    Password(String),
 }
 
 fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
@@ -247,7 +393,12 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             error!(format!("Fetch error: {:#?}", fail_reason));
         }
         Msg::EntrySearchResult(Ok(res)) => {
-            model.entries = res.visible;
+            let center = bbox_center(model.bbox);
+            model.entries = res
+                .visible
+                .into_iter()
+                .filter(|e| model.filter.matches(e, center))
+                .collect();
             updateMap(JsValue::from_serde(&model.entries).unwrap());
         }
         Msg::EntrySearchResult(Err(fail_reason)) => {
@@ -260,7 +411,11 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
         }
         Msg::UpdateBBox(bbox) => {
             log!("update bbox in WASM");
-            orders.perform_cmd(fetch_entries(&bbox));
+            if model.live_mode {
+                stream::resubscribe(&bbox);
+            } else {
+                orders.perform_cmd(fetch_entries(&bbox, &model.filter, &model.search_query));
+            }
             model.bbox = Some(bbox);
         }
         Msg::EntrySelected(id) => {
@@ -277,15 +432,155 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             EntryFormMsg::Description(txt) => {
                 model.new_entry_form.description = txt;
             }
+            EntryFormMsg::Photos(files) => {
+                model.new_entry_form.photos = files;
+            }
         },
         Msg::CreateNewEntry => match model.new_entry_form.validate() {
             Ok(_) => {
                 log!("create new entry", model.new_entry_form);
+                model.new_entry_form_errors = vec![];
+                let (lat, lng) = bbox_center(model.bbox);
+                orders.perform_cmd(create_entry(&model.new_entry_form, lat, lng, &model.session));
             }
             Err(err) => {
                 model.new_entry_form_errors = err.into_iter().collect();
             }
         },
+        Msg::EntryCreated(Ok(res)) => {
+            let (lat, lng) = bbox_center(model.bbox);
+            let entry = Entry {
+                id: res.id,
+                title: model.new_entry_form.title.clone(),
+                description: model.new_entry_form.description.clone(),
+                lat,
+                lng,
+            };
+            model.show_new_entry_form = false;
+            model.new_entry_form = EntryFormModel::default();
+            model.new_entry_form_errors = vec![];
+            let activity = activitystreams::entry_to_create_activity(&entry, ACTOR);
+            orders.perform_cmd(activitystreams::publish(&activity, &model.outbox_url));
+            if let Some(bbox) = model.bbox {
+                orders.perform_cmd(fetch_entries(&bbox, &model.filter, &model.search_query));
+            }
+        }
+        Msg::EntryCreated(Err(fail_reason)) => {
+            model.new_entry_form_errors = vec![EntryFormInvalidity::Server(fail_reason)];
+        }
+        Msg::ActivityPublished(Ok(())) => {
+            log!("activity published to outbox");
+        }
+        Msg::ActivityPublished(Err(fail_reason)) => {
+            error!(format!("Federation publish error: {:#?}", fail_reason));
+        }
+        Msg::RemoteActivity(Ok(activity)) => match activitystreams::apply_remote_activity(&mut model.entries, activity) {
+            activitystreams::Applied::Upserted(entry) => {
+                log!("merged remote activity", entry);
+                upsertMapEntry(JsValue::from_serde(&entry).unwrap());
+            }
+            activitystreams::Applied::Removed(id) => {
+                log!("removed remote activity", id);
+                removeMapEntry(id);
+            }
+            activitystreams::Applied::Ignored => {}
+        },
+        Msg::RemoteActivity(Err(fail_reason)) => {
+            error!(format!("Inbox decode error: {:#?}", fail_reason));
+        }
+        Msg::ShowLoginForm => {
+            model.show_login_form = true;
+        }
+        Msg::LoginForm(e_msg) => match e_msg {
+            LoginFormMsg::Email(txt) => {
+                model.login_form.email = txt;
+            }
+            LoginFormMsg::Password(txt) => {
+                model.login_form.password = txt;
+            }
+        },
+        Msg::Login => {
+            orders.perform_cmd(session::login(
+                model.login_form.email.clone(),
+                model.login_form.password.clone(),
+            ));
+        }
+        Msg::LoginResult(Ok(session)) => {
+            model.session = Some(session);
+            model.show_login_form = false;
+            model.login_form = LoginFormModel::default();
+            model.login_form_error = None;
+        }
+        Msg::LoginResult(Err(fail_reason)) => {
+            model.login_form_error = Some(fail_reason);
+        }
+        Msg::Logout => {
+            model.session = None;
+        }
+        Msg::ToggleLiveMode => {
+            model.live_mode = !model.live_mode;
+            if model.live_mode {
+                if let Some(bbox) = model.bbox {
+                    stream::resubscribe(&bbox);
+                }
+            } else if let Some(bbox) = model.bbox {
+                orders.perform_cmd(fetch_entries(&bbox, &model.filter, &model.search_query));
+            }
+        }
+        Msg::StreamEvent(Ok(event)) => match stream::apply_event(&mut model.entries, event) {
+            stream::Applied::Upserted(entry) => upsertMapEntry(JsValue::from_serde(&entry).unwrap()),
+            stream::Applied::Removed(id) => removeMapEntry(id),
+        },
+        Msg::StreamEvent(Err(fail_reason)) => {
+            error!(format!("Stream decode error: {:#?}", fail_reason));
+        }
+        Msg::SearchQuery(txt) => {
+            model.search_query = txt;
+        }
+        Msg::RankTextWeight(weight) => {
+            model.rank_text_weight = weight;
+        }
+        Msg::Export(format) => {
+            export::download(&model.entries, format);
+            orders.skip();
+        }
+        Msg::ToggleFilterCategory(idx) => {
+            if let Some(category) = model.filter.categories.get_mut(idx) {
+                category.selected = !category.selected;
+            }
+            if let Some(bbox) = model.bbox {
+                orders.perform_cmd(fetch_entries(&bbox, &model.filter, &model.search_query));
+            }
+        }
+        Msg::FilterMinRating(min_rating) => {
+            model.filter.min_rating = min_rating;
+            if let Some(bbox) = model.bbox {
+                orders.perform_cmd(fetch_entries(&bbox, &model.filter, &model.search_query));
+            }
+        }
+        Msg::FilterMaxDistanceKm(max_distance) => {
+            model.filter.max_distance_km = max_distance;
+            // Trim the current list immediately as a visual optimization;
+            // the refetch below is still the source of truth, since a
+            // widened/cleared filter can only restore entries via the API.
+            let filter = model.filter.clone();
+            let center = bbox_center(model.bbox);
+            model.entries.retain(|e| filter.matches(e, center));
+            updateMap(JsValue::from_serde(&model.entries).unwrap());
+            if let Some(bbox) = model.bbox {
+                orders.perform_cmd(fetch_entries(&bbox, &model.filter, &model.search_query));
+            }
+        }
+    }
+}
+
+fn bbox_center(bbox: Option<BBox>) -> (f64, f64) {
+    match bbox {
+        Some(bbox) => (
+            (bbox.north_east.lat + bbox.south_west.lat) / 2.0,
+            (bbox.north_east.lng + bbox.south_west.lng) / 2.0,
+        ),
+        None => (0.0, 0.0),
     }
 }
 
@@ -296,6 +591,21 @@ fn view(model: &Model) -> impl View<Msg> {
             attrs! { At::Type => "text"; At::Placeholder => "which place would you like to discover?";},
             input_ev(Ev::Input, Msg::CitySearch)
         ],
+        button![
+            simple_ev(Ev::Click, Msg::ToggleLiveMode),
+            if model.live_mode {
+                "disable live updates"
+            } else {
+                "enable live updates"
+            }
+        ],
+        if model.session.is_some() {
+            div!["logged in", button![simple_ev(Ev::Click, Msg::Logout), "logout"]]
+        } else if model.show_login_form {
+            login_form(&model.login_form, &model.login_form_error)
+        } else {
+            button![simple_ev(Ev::Click, Msg::ShowLoginForm), "login"]
+        },
         if model.show_new_entry_form {
             new_entry_form(&model.new_entry_form, &model.new_entry_form_errors)
         } else {
@@ -317,6 +627,68 @@ fn view(model: &Model) -> impl View<Msg> {
         } else {
             seed::empty!()
         },
+        input![
+            attrs! { At::Type => "text"; At::Placeholder => "search entries";},
+            input_ev(Ev::Input, Msg::SearchQuery)
+        ],
+        label![
+            "near me",
+            input![
+                attrs! {At::Type=>"range"; At::Min=>"0"; At::Max=>"2"; At::Step=>"0.1"; At::Value=>model.rank_text_weight.to_string();},
+                input_ev(Ev::Input, |v| Msg::RankTextWeight(v.parse().unwrap_or(1.0)))
+            ],
+            "best match"
+        ],
+        ul![ranking::rank(
+            &model.entries,
+            &model.search_query,
+            bbox_center(model.bbox),
+            model.rank_text_weight
+        )
+        .iter()
+        .map(|e| li![
+            simple_ev(Ev::Click, Msg::EntrySelected(e.id.clone())),
+            e.title.clone()
+        ])
+        .collect::<Vec<_>>()],
+        div![
+            attrs! {At::Class=>"filters"},
+            model
+                .filter
+                .categories
+                .iter()
+                .enumerate()
+                .map(|(idx, c)| label![
+                    input![
+                        attrs! {At::Type=>"checkbox"; At::Checked=>c.selected;},
+                        simple_ev(Ev::Click, Msg::ToggleFilterCategory(idx))
+                    ],
+                    c.label
+                ])
+                .collect::<Vec<_>>(),
+            label![
+                "min. rating",
+                input![
+                    attrs! {At::Type=>"number"; At::Min=>"0"; At::Max=>"1"; At::Step=>"0.1";},
+                    input_ev(Ev::Input, |v| Msg::FilterMinRating(v.parse().ok()))
+                ]
+            ],
+            label![
+                "max. distance (km)",
+                input![
+                    attrs! {At::Type=>"number"; At::Min=>"0";},
+                    input_ev(Ev::Input, |v| Msg::FilterMaxDistanceKm(v.parse().ok()))
+                ]
+            ],
+        ],
+        button![
+            simple_ev(Ev::Click, Msg::Export(export::Format::GeoJson)),
+            "export GeoJSON"
+        ],
+        button![
+            simple_ev(Ev::Click, Msg::Export(export::Format::Csv)),
+            "export CSV"
+        ],
     ]
 }
 
@@ -337,6 +709,7 @@ fn new_entry_form(m: &EntryFormModel, errors: &[EntryFormInvalidity]) -> Node<Ms
                         "Title too short: {} characters, minimum: {}",
                         actual.0, min.0
                     )),
+                    _ => None,
                 })
                 .nth(0)
             {
@@ -355,12 +728,99 @@ fn new_entry_form(m: &EntryFormModel, errors: &[EntryFormInvalidity]) -> Node<Ms
                 ))),
                 m.description,
             ],
+            if let Some(msg) = errors
+                .iter()
+                .filter_map(|i| match i {
+                    EntryFormInvalidity::DescriptionLength(max, actual) => Some(format!(
+                        "Description too long: {} characters, maximum: {}",
+                        actual.0, max.0
+                    )),
+                    _ => None,
+                })
+                .nth(0)
+            {
+                div![attrs! {At::Style=>"color:red;"}, msg]
+            } else {
+                seed::empty()
+            }
         ],
         br![],
+        label![
+            "Photos",
+            br![],
+            input![
+                attrs! {At::Type=>"file"; At::Multiple=>true; At::Accept=>ALLOWED_PHOTO_MIME_TYPES.join(",");},
+                raw_ev(Ev::Change, |event| {
+                    let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                    let files = input
+                        .files()
+                        .map(|list| (0..list.length()).filter_map(|i| list.get(i)).collect())
+                        .unwrap_or_default();
+                    Msg::EntryForm(EntryFormMsg::Photos(files))
+                })
+            ],
+            if let Some(msg) = errors.iter().find_map(|i| match i {
+                EntryFormInvalidity::PhotoTooLarge(max, actual) => Some(format!(
+                    "Photo too large: {} bytes, maximum: {} bytes",
+                    actual.0, max.0
+                )),
+                EntryFormInvalidity::PhotoInvalidMimeType(mime) => {
+                    Some(format!("Unsupported photo type: {}", mime))
+                }
+                _ => None,
+            }) {
+                div![attrs! {At::Style=>"color:red;"}, msg]
+            } else {
+                seed::empty()
+            }
+        ],
+        br![],
+        if let Some(msg) = errors
+            .iter()
+            .filter_map(|i| match i {
+                EntryFormInvalidity::Server(msg) => Some(msg.clone()),
+                _ => None,
+            })
+            .nth(0)
+        {
+            div![attrs! {At::Style=>"color:red;"}, msg]
+        } else {
+            seed::empty()
+        },
         button![simple_ev(Ev::Click, Msg::CreateNewEntry), "create"]
     ]
 }
 
+fn login_form(m: &LoginFormModel, error: &Option<String>) -> Node<Msg> {
+    div![
+        attrs! {At::Class=>"form"},
+        label![
+            "Email",
+            br![],
+            input![
+                attrs! {At::Type=>"email"; At::Value=> m.email;},
+                input_ev(Ev::Input, |txt| Msg::LoginForm(LoginFormMsg::Email(txt)))
+            ],
+        ],
+        br![],
+        label![
+            "Password",
+            br![],
+            input![
+                attrs! {At::Type=>"password"; At::Value=> m.password;},
+                input_ev(Ev::Input, |txt| Msg::LoginForm(LoginFormMsg::Password(txt)))
+            ],
+        ],
+        br![],
+        if let Some(ref msg) = error {
+            div![attrs! {At::Style=>"color:red;"}, msg.clone()]
+        } else {
+            seed::empty()
+        },
+        button![simple_ev(Ev::Click, Msg::Login), "login"]
+    ]
+}
+
 #[wasm_bindgen]
 pub fn start() -> Box<[JsValue]> {
     let app = seed::App::build(|_, _| Model::default(), update, view)
@@ -381,7 +841,16 @@ pub fn start() -> Box<[JsValue]> {
     let update_bbox = update_bbox_closure.as_ref().clone();
     update_bbox_closure.forget();
 
-    vec![marker_clicked, update_bbox].into_boxed_slice()
+    let app_clone = app.clone();
+    let inbox_activity_closure = Closure::new(move |json: String| {
+        inbox_activity(json, app_clone.clone());
+    });
+    let inbox_activity = inbox_activity_closure.as_ref().clone();
+    inbox_activity_closure.forget();
+
+    stream::init(app);
+
+    vec![marker_clicked, update_bbox, inbox_activity].into_boxed_slice()
 }
 
 fn update_bbox<V: View<Msg> + 'static>(bbox: BBox, app: seed::App<Msg, Model, V>) {
@@ -400,8 +869,16 @@ fn marker_clicked<V: View<Msg> + 'static>(id: String, app: seed::App<Msg, Model,
     app.update(Msg::EntrySelected(id));
 }
 
+fn inbox_activity<V: View<Msg> + 'static>(json: String, app: seed::App<Msg, Model, V>) {
+    log!("inbox activity", json);
+    let activity = serde_json::from_str(&json).map_err(|e| format!("{:#?}", e));
+    app.update(Msg::RemoteActivity(activity));
+}
+
 #[wasm_bindgen]
 extern "C" {
     fn setMapCenter(lat: f64, lng: f64);
     fn updateMap(map_entries: JsValue);
+    fn upsertMapEntry(map_entry: JsValue);
+    fn removeMapEntry(id: String);
 }
@@ -0,0 +1,110 @@
+//! Hybrid text + proximity ranking for the entry list.
+//!
+//! Two ranked lists — textual relevance against the user's query, and
+//! geographic proximity to the map center — are fused with reciprocal
+//! rank fusion rather than picking one signal outright.
+
+use crate::Entry;
+
+const K: f64 = 60.0;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great-circle distance between two lat/lng points, in kilometers.
+pub fn haversine_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lng2 - lng1).to_radians();
+    let phi1 = lat1.to_radians();
+    let phi2 = lat2.to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Case-insensitive token overlap / prefix match of `query` against an
+/// entry's title and description. Higher is more relevant; zero means no
+/// overlap at all.
+fn text_score(query: &str, entry: &Entry) -> f64 {
+    if query.is_empty() {
+        return 0.0;
+    }
+    let haystack = format!("{} {}", entry.title, entry.description).to_lowercase();
+    let haystack_tokens: Vec<&str> = haystack.split_whitespace().collect();
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .map(|token| {
+            if haystack_tokens.iter().any(|word| *word == token) {
+                2.0
+            } else if haystack_tokens.iter().any(|word| word.starts_with(token)) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .sum()
+}
+
+/// Ranks `entries` by fusing text relevance against `query` and proximity
+/// to `center` via reciprocal rank fusion (`k = 60`). `text_weight` scales
+/// the text list's contribution, so `1.0` weighs both lists equally and
+/// lower values bias toward "near me" over "best match".
+pub fn rank(entries: &[Entry], query: &str, center: (f64, f64), text_weight: f64) -> Vec<Entry> {
+    let mut by_text: Vec<&Entry> = entries.iter().filter(|e| text_score(query, e) > 0.0).collect();
+    by_text.sort_by(|a, b| {
+        text_score(query, b)
+            .partial_cmp(&text_score(query, a))
+            .unwrap()
+    });
+
+    let distance_to = |e: &Entry| haversine_km(center.0, center.1, e.lat, e.lng);
+    let mut by_distance: Vec<&Entry> = entries.iter().collect();
+    by_distance.sort_by(|a, b| distance_to(a).partial_cmp(&distance_to(b)).unwrap());
+
+    let mut fused: Vec<(f64, f64, Entry)> = entries
+        .iter()
+        .map(|entry| {
+            let mut score = 0.0;
+            if let Some(rank) = by_text.iter().position(|e| e.id == entry.id) {
+                score += text_weight / (K + rank as f64);
+            }
+            if let Some(rank) = by_distance.iter().position(|e| e.id == entry.id) {
+                score += 1.0 / (K + rank as f64);
+            }
+            (score, distance_to(entry), entry.clone())
+        })
+        .collect();
+
+    fused.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.partial_cmp(&b.1).unwrap()));
+    fused.into_iter().map(|(_, _, entry)| entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_at(id: &str, lat: f64, lng: f64) -> Entry {
+        Entry {
+            id: id.into(),
+            title: "unrelated".into(),
+            description: "unrelated".into(),
+            lat,
+            lng,
+        }
+    }
+
+    /// Non-matching entries must fall back to pure distance order, not the
+    /// incidental position they happened to hold in the input array.
+    #[test]
+    fn non_matching_entries_rank_by_distance_not_input_order() {
+        let center = (0.0, 0.0);
+        // Ordered D, E, C in the input, but C is closest and E is farthest.
+        let d = entry_at("d", 0.045, 0.0); // ~5km
+        let e = entry_at("e", 0.45, 0.0); // ~50km
+        let c = entry_at("c", 0.009, 0.0); // ~1km
+        let entries = vec![d.clone(), e.clone(), c.clone()];
+
+        let ranked = rank(&entries, "no match here", center, 1.0);
+        let ids: Vec<&str> = ranked.iter().map(|e| e.id.as_str()).collect();
+
+        assert_eq!(ids, vec!["c", "d", "e"]);
+    }
+}
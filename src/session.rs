@@ -0,0 +1,51 @@
+//! Session handling for authenticated OFDB write requests.
+//!
+//! Holds the bearer token obtained from the OFDB login endpoint and
+//! attaches it as an `Authorization` header to outgoing write requests.
+
+use crate::Msg;
+use futures::Future;
+use seed::prelude::*;
+use serde::{Deserialize, Serialize};
+use web_sys::Request;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LoginRequest {
+    email: String,
+    password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LoginResponse {
+    token: String,
+}
+
+pub fn login(email: String, password: String) -> impl Future<Item = Msg, Error = Msg> {
+    log!("login", email);
+    let body = LoginRequest { email, password };
+    seed::fetch::Request::new("https://api.ofdb.io/v0/login")
+        .method(seed::fetch::Method::Post)
+        .send_json(&body)
+        .fetch_json_data(|d: seed::fetch::ResponseDataResult<LoginResponse>| {
+            Msg::LoginResult(
+                d.map(|r| Session { token: r.token })
+                    .map_err(|e| format!("{:#?}", e)),
+            )
+        })
+}
+
+/// Attaches the bearer token to a request if a session is present, leaving
+/// anonymous requests untouched.
+pub fn authorize(request: &Request, session: &Option<Session>) {
+    if let Some(session) = session {
+        request
+            .headers()
+            .set("Authorization", &format!("Bearer {}", session.token))
+            .unwrap();
+    }
+}
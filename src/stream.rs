@@ -0,0 +1,135 @@
+//! Real-time entry updates over a WebSocket, replacing repeated one-shot
+//! `fetch_entries` calls while live mode is enabled.
+//!
+//! A single long-lived connection is opened from `start()` and kept alive
+//! for the whole session; a bbox change re-subscribes the existing socket
+//! instead of reconnecting, and a dropped connection is retried with
+//! exponential backoff.
+
+use crate::{BBox, Entry, Model, Msg};
+use seed::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CloseEvent, Event as DomEvent, MessageEvent, WebSocket};
+
+const STREAM_URL: &str = "wss://api.ofdb.io/v0/entries/stream";
+const INITIAL_BACKOFF_MS: i32 = 500;
+const MAX_BACKOFF_MS: i32 = 30_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Event {
+    Created(Entry),
+    Updated(Entry),
+    Deleted { id: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Command {
+    Subscribe { bbox: Vec<f64> },
+}
+
+/// What a decoded stream event did to `entries`, so the caller can patch
+/// just the affected marker instead of redrawing the whole map.
+pub enum Applied {
+    Upserted(Entry),
+    Removed(String),
+}
+
+/// Applies a decoded stream event to `entries`.
+pub fn apply_event(entries: &mut Vec<Entry>, event: Event) -> Applied {
+    match event {
+        Event::Created(entry) | Event::Updated(entry) => {
+            entries.retain(|e| e.id != entry.id);
+            entries.push(entry.clone());
+            Applied::Upserted(entry)
+        }
+        Event::Deleted { id } => {
+            entries.retain(|e| e.id != id);
+            Applied::Removed(id)
+        }
+    }
+}
+
+thread_local! {
+    static SOCKET: RefCell<Option<WebSocket>> = RefCell::new(None);
+    static BACKOFF_MS: RefCell<i32> = RefCell::new(INITIAL_BACKOFF_MS);
+    static LAST_BBOX: RefCell<Option<BBox>> = RefCell::new(None);
+}
+
+/// Opens the streaming connection. Safe to call once from `start()`; the
+/// connection then lives for the rest of the session and reconnects itself
+/// on drop.
+pub fn init<V: View<Msg> + 'static>(app: seed::App<Msg, Model, V>) {
+    open(app);
+}
+
+/// Re-subscribes the existing connection to a new bbox. Remembers `bbox`
+/// regardless of socket state, so a socket that's still `CONNECTING` (the
+/// handshake is async even though `WebSocket::new` isn't) or mid-reconnect
+/// picks up the subscription as soon as it opens, instead of losing it.
+pub fn resubscribe(bbox: &BBox) {
+    LAST_BBOX.with(|last| *last.borrow_mut() = Some(*bbox));
+    send_subscribe(bbox);
+}
+
+fn send_subscribe(bbox: &BBox) {
+    SOCKET.with(|socket| {
+        if let Some(ref ws) = *socket.borrow() {
+            if ws.ready_state() == WebSocket::OPEN {
+                let cmd = Command::Subscribe { bbox: bbox.to_vec() };
+                if let Ok(json) = serde_json::to_string(&cmd) {
+                    let _ = ws.send_with_str(&json);
+                }
+            }
+        }
+    });
+}
+
+fn open<V: View<Msg> + 'static>(app: seed::App<Msg, Model, V>) {
+    let ws = match WebSocket::new(STREAM_URL) {
+        Ok(ws) => ws,
+        Err(e) => {
+            error!(format!("Stream connect error: {:#?}", e));
+            return;
+        }
+    };
+
+    let onopen = Closure::wrap(Box::new(move |_e: DomEvent| {
+        BACKOFF_MS.with(|backoff| *backoff.borrow_mut() = INITIAL_BACKOFF_MS);
+        if let Some(bbox) = LAST_BBOX.with(|last| *last.borrow()) {
+            send_subscribe(&bbox);
+        }
+    }) as Box<dyn FnMut(DomEvent)>);
+    ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    onopen.forget();
+
+    let app_clone = app.clone();
+    let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+        if let Some(text) = e.data().as_string() {
+            let event = serde_json::from_str::<Event>(&text).map_err(|e| format!("{:#?}", e));
+            app_clone.update(Msg::StreamEvent(event));
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    let app_clone = app.clone();
+    let onclose = Closure::wrap(Box::new(move |_e: CloseEvent| {
+        SOCKET.with(|socket| *socket.borrow_mut() = None);
+        let delay = BACKOFF_MS.with(|backoff| *backoff.borrow());
+        let app_retry = app_clone.clone();
+        seed::set_timeout(Box::new(move || open(app_retry)), delay);
+        BACKOFF_MS.with(|backoff| {
+            let mut backoff = backoff.borrow_mut();
+            *backoff = (*backoff * 2).min(MAX_BACKOFF_MS);
+        });
+    }) as Box<dyn FnMut(CloseEvent)>);
+    ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+    onclose.forget();
+
+    SOCKET.with(|socket| *socket.borrow_mut() = Some(ws));
+}